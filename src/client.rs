@@ -2,32 +2,59 @@ use bytes::{Buf, BytesMut};
 use futures::prelude::*;
 use futures::sink::SinkExt;
 use std::net::ToSocketAddrs;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
 use tokio_util::codec::{Decoder, Encoder, Framed};
 
 use native_tls::TlsConnector as NativeTlsConnector;
 use tokio_native_tls::{TlsConnector, TlsStream};
 
-pub type ClientTransport = Framed<TcpStream, ClientCodec>;
-pub type ClientTlsTransport = Framed<TlsStream<TcpStream>, ClientCodec>;
+#[cfg(feature = "rustls")]
+use std::sync::Arc;
+#[cfg(feature = "rustls")]
+use tokio_rustls::{rustls, rustls::pki_types::ServerName};
+
+/// A connected, authenticated STOMP transport: a [`Stream`] of
+/// `Result<Message<FromServer>>` and a [`Sink`] of `Message<ToServer>`,
+/// backed by [`crate::heartbeat::HeartbeatTransport`] so that the negotiated
+/// heart-beat send/liveness tasks (if any) run for the lifetime of the
+/// connection regardless of which `connect*` function produced it.
+pub type ClientTransport =
+    crate::heartbeat::HeartbeatTransport<Message<ToServer>, Message<FromServer>>;
+pub type ClientTlsTransport =
+    crate::heartbeat::HeartbeatTransport<Message<ToServer>, Message<FromServer>>;
+#[cfg(feature = "rustls")]
+pub type ClientRustlsTransport =
+    crate::heartbeat::HeartbeatTransport<Message<ToServer>, Message<FromServer>>;
 
 use crate::frame;
+use crate::heartbeat::Heartbeat;
 use crate::{FromServer, Message, Result, ToServer};
 use anyhow::{anyhow, bail};
 
 /// Connect to a STOMP server via TCP, including the connection handshake.
 /// If successful, returns a tuple of a message stream and a sender,
 /// which may be used to receive and send messages respectively.
+///
+/// `heartbeat`, if given, is the `(cx, cy)` pair (in milliseconds) to
+/// request: `cx` is the minimum interval between frames we guarantee to
+/// send, `cy` is the interval we want from the server. A `0` disables that
+/// direction. See [`crate::heartbeat`] for how the effective intervals are
+/// negotiated and enforced.
 pub async fn connect(
     address: &str,
     login: Option<String>,
     passcode: Option<String>,
+    heartbeat: Option<(u32, u32)>,
 ) -> Result<ClientTransport> {
     let addr = address.to_socket_addrs().unwrap().next().unwrap();
     let tcp = TcpStream::connect(&addr).await?;
-    let mut transport = ClientCodec.framed(tcp);
-    client_handshake(&mut transport, address, login, passcode).await?;
-    Ok(transport)
+    let (io, last_seen) = crate::heartbeat::wrap(tcp);
+    let mut transport = ClientCodec.framed(io);
+    let negotiated = client_handshake(&mut transport, address, login, passcode, heartbeat).await?;
+    Ok(crate::heartbeat::HeartbeatTransport::spawn(
+        transport, last_seen, negotiated,
+    ))
 }
 
 pub async fn connect_tls(
@@ -35,61 +62,130 @@ pub async fn connect_tls(
     address: &str,
     login: Option<String>,
     passcode: Option<String>,
+    tls_config: TlsConfig,
+    heartbeat: Option<(u32, u32)>,
 ) -> Result<ClientTlsTransport> {
     let addr = address.to_socket_addrs()?.next().unwrap();
     // Set up the TLS connector
-    let native_tls_connector = NativeTlsConnector::builder()
-        .danger_accept_invalid_certs(true)
-        .build()?;
+    let mut builder = NativeTlsConnector::builder();
+    builder
+        .danger_accept_invalid_certs(tls_config.accept_invalid_certs)
+        .danger_accept_invalid_hostnames(tls_config.accept_invalid_hostnames);
+    for cert in tls_config.root_certificates {
+        builder.add_root_certificate(cert);
+    }
+    if let Some(identity) = tls_config.identity {
+        builder.identity(identity);
+    }
+    let native_tls_connector = builder.build()?;
     let tls_connector = TlsConnector::from(native_tls_connector);
     let tcp_stream = TcpStream::connect(&addr).await?;
     // Perform the TLS handshake
     let tls_stream: TlsStream<TcpStream> = tls_connector.connect(domain, tcp_stream).await?;
-    let mut transport = ClientCodec.framed(tls_stream);
-    client_handshake_tls(&mut transport, address, login, passcode).await?;
-    Ok(transport)
+    let (io, last_seen) = crate::heartbeat::wrap(tls_stream);
+    let mut transport = ClientCodec.framed(io);
+    let negotiated = client_handshake(&mut transport, address, login, passcode, heartbeat).await?;
+    Ok(crate::heartbeat::HeartbeatTransport::spawn(
+        transport, last_seen, negotiated,
+    ))
+}
+
+/// Configuration for [`connect_tls`].
+///
+/// Defaults to full certificate and hostname verification; the insecure
+/// options must be opted into explicitly.
+#[derive(Default, Clone)]
+pub struct TlsConfig {
+    root_certificates: Vec<native_tls::Certificate>,
+    identity: Option<native_tls::Identity>,
+    accept_invalid_certs: bool,
+    accept_invalid_hostnames: bool,
+}
+
+impl TlsConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a trusted root certificate (e.g. loaded from a DER or PEM file)
+    /// in addition to the platform's native root store.
+    pub fn add_root_certificate(mut self, cert: native_tls::Certificate) -> Self {
+        self.root_certificates.push(cert);
+        self
+    }
+
+    /// Present a client identity (PKCS#12) during the handshake, for mutual
+    /// TLS authentication.
+    pub fn identity(mut self, identity: native_tls::Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Disable certificate validation entirely. Dangerous: only intended for
+    /// testing against servers with self-signed or otherwise untrusted certs.
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Disable hostname verification. Dangerous: allows a cert for any
+    /// hostname to be accepted.
+    pub fn danger_accept_invalid_hostnames(mut self, accept: bool) -> Self {
+        self.accept_invalid_hostnames = accept;
+        self
+    }
 }
 
-async fn client_handshake(
-    transport: &mut ClientTransport,
+/// Connect to a STOMP server via TLS using `rustls` instead of `native-tls`.
+///
+/// Unlike [`connect_tls`], this performs no certificate handling of its own:
+/// callers build and pass in a fully configured `rustls::ClientConfig`, which
+/// is how the root store, ALPN protocols, and crypto provider (`aws_lc_rs`,
+/// `ring`, or a custom one installed via `rustls::crypto::CryptoProvider::install_default`)
+/// are controlled.
+#[cfg(feature = "rustls")]
+pub async fn connect_rustls(
+    domain: &str,
     address: &str,
     login: Option<String>,
     passcode: Option<String>,
-) -> Result<()> {
-    let connect = Message {
-        content: ToServer::Connect {
-            accept_version: "1.2".into(),
-            host: address.to_string(),
-            login,
-            passcode,
-            heartbeat: None,
-        },
-        extra_headers: vec![],
-    };
-    // Send the message
-    transport.send(connect).await?;
-    // Receive reply
-    let msg = transport.next().await.transpose()?;
-    if let Some(FromServer::Connected { .. }) = msg.as_ref().map(|m| &m.content) {
-        Ok(())
-    } else {
-        Err(anyhow!("unexpected reply: {:?}", msg))
-    }
+    config: Arc<rustls::ClientConfig>,
+    heartbeat: Option<(u32, u32)>,
+) -> Result<ClientRustlsTransport> {
+    let addr = address.to_socket_addrs()?.next().unwrap();
+    let server_name = ServerName::try_from(domain.to_string())
+        .map_err(|_| anyhow!("invalid DNS name: {domain}"))?;
+    let tls_connector = tokio_rustls::TlsConnector::from(config);
+    let tcp_stream = TcpStream::connect(&addr).await?;
+    let tls_stream = tls_connector.connect(server_name, tcp_stream).await?;
+    let (io, last_seen) = crate::heartbeat::wrap(tls_stream);
+    let mut transport = ClientCodec.framed(io);
+    let negotiated = client_handshake(&mut transport, address, login, passcode, heartbeat).await?;
+    Ok(crate::heartbeat::HeartbeatTransport::spawn(
+        transport, last_seen, negotiated,
+    ))
 }
 
-async fn client_handshake_tls(
-    transport: &mut ClientTlsTransport,
+/// Send the CONNECT frame and wait for CONNECTED, generic over the
+/// underlying transport so `connect`, `connect_tls`, and `connect_rustls`
+/// can share one handshake. Mirrors [`crate::server::server_handshake`].
+async fn client_handshake<T>(
+    transport: &mut Framed<crate::heartbeat::LivenessIo<T>, ClientCodec>,
     address: &str,
     login: Option<String>,
     passcode: Option<String>,
-) -> Result<()> {
+    heartbeat: Option<(u32, u32)>,
+) -> Result<Heartbeat>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
     let connect = Message {
         content: ToServer::Connect {
             accept_version: "1.2".into(),
             host: address.to_string(),
             login,
             passcode,
-            heartbeat: None,
+            heartbeat,
         },
         extra_headers: vec![],
     };
@@ -97,10 +193,14 @@ async fn client_handshake_tls(
     transport.send(connect).await?;
     // Receive reply
     let msg = transport.next().await.transpose()?;
-    if let Some(FromServer::Connected { .. }) = msg.as_ref().map(|m| &m.content) {
-        Ok(())
-    } else {
-        Err(anyhow!("unexpected reply: {:?}", msg))
+    match msg.as_ref().map(|m| &m.content) {
+        Some(FromServer::Connected {
+            heartbeat: server, ..
+        }) => Ok(Heartbeat::negotiate(
+            heartbeat.unwrap_or((0, 0)),
+            server.unwrap_or((0, 0)),
+        )),
+        _ => Err(anyhow!("unexpected reply: {:?}", msg)),
     }
 }
 
@@ -114,6 +214,27 @@ pub fn subscribe(dest: impl Into<String>, id: impl Into<String>) -> Message<ToSe
     .into()
 }
 
+/// Skip leading bare `\n`/`\r\n` heart-beat bytes from the front of `src`.
+/// Returns `true` if the decoder should return `Ok(None)` and wait for more
+/// data rather than attempting to parse a frame: either the buffer is now
+/// empty, or it ends in a lone `\r` that might be the start of a `\r\n`
+/// heart-beat still in flight. Shared by [`ClientCodec`] and
+/// [`crate::server::ServerCodec`].
+pub(crate) fn skip_heartbeats(src: &mut BytesMut) -> bool {
+    loop {
+        match src.first() {
+            Some(b'\n') => src.advance(1),
+            Some(b'\r') => match src.get(1) {
+                Some(b'\n') => src.advance(2),
+                Some(_) => break,
+                None => return true,
+            },
+            _ => break,
+        }
+    }
+    src.is_empty()
+}
+
 pub struct ClientCodec;
 
 impl Decoder for ClientCodec {
@@ -121,6 +242,9 @@ impl Decoder for ClientCodec {
     type Error = anyhow::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>> {
+        if skip_heartbeats(src) {
+            return Ok(None);
+        }
         let (item, offset) = match frame::parse_frame(src) {
             Ok((remain, frame)) => (
                 Message::<FromServer>::from_frame(frame),
@@ -146,3 +270,42 @@ impl Encoder<Message<ToServer>> for ClientCodec {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skip_heartbeats_consumes_bare_newlines() {
+        let mut buf = BytesMut::from(&b"\n\n\r\n"[..]);
+        assert!(skip_heartbeats(&mut buf));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn skip_heartbeats_waits_on_partial_cr_at_boundary() {
+        // A `\r\n` heart-beat split across two reads: only the `\r` has
+        // arrived so far, and we can't yet tell whether it's a heart-beat or
+        // garbage. Must wait for more data rather than handing a lone `\r` to
+        // the frame parser.
+        let mut buf = BytesMut::from(&b"\r"[..]);
+        assert!(skip_heartbeats(&mut buf));
+        assert_eq!(&buf[..], b"\r");
+    }
+
+    #[test]
+    fn skip_heartbeats_leaves_non_heartbeat_cr_alone() {
+        // `\r` not followed by `\n` is not a heart-beat; leave it for the
+        // frame parser rather than skipping it.
+        let mut buf = BytesMut::from(&b"\rCONNECT\n"[..]);
+        assert!(!skip_heartbeats(&mut buf));
+        assert_eq!(&buf[..], b"\rCONNECT\n");
+    }
+
+    #[test]
+    fn skip_heartbeats_stops_at_a_real_frame() {
+        let mut buf = BytesMut::from(&b"\n\nCONNECTED\n\n\0"[..]);
+        assert!(!skip_heartbeats(&mut buf));
+        assert_eq!(&buf[..], b"CONNECTED\n\n\0");
+    }
+}
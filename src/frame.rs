@@ -0,0 +1,370 @@
+//! Raw STOMP frame representation and wire (de)serialization.
+//!
+//! A [`Frame`] is the on-the-wire shape of a STOMP message: a command line,
+//! a block of `key:value` header lines, a blank line, and a body terminated
+//! by a NUL byte. [`parse_frame`] turns bytes into a `Frame`; [`Frame::serialize`]
+//! does the reverse. [`ToFrame`]/[`FromFrame`] convert between a `Frame` and
+//! the typed [`crate::ToServer`]/[`crate::FromServer`] content enums, and are
+//! used by [`crate::client::ClientCodec`] and [`crate::server::ServerCodec`].
+//!
+//! Callers are expected to have already skipped any leading heart-beat bytes
+//! (see `skip_heartbeats` in [`crate::client`]) before calling [`parse_frame`].
+
+use anyhow::{anyhow, bail};
+use bytes::{BufMut, BytesMut};
+use nom::{
+    bytes::streaming::{is_not, tag, take_until},
+    multi::many0,
+    sequence::terminated,
+    IResult,
+};
+
+use crate::{AckMode, FromServer, Message, Result, ToServer};
+
+/// A parsed (or about-to-be-serialized) STOMP frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    command: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Frame {
+    fn new(
+        command: &'static str,
+        headers: Vec<(&'static str, Option<String>)>,
+        body: Vec<u8>,
+    ) -> Frame {
+        let headers = headers
+            .into_iter()
+            .filter_map(|(k, v)| v.map(|v| (k.to_string(), v)))
+            .collect();
+        Frame {
+            command: command.to_string(),
+            headers,
+            body,
+        }
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn require_header(&self, name: &str) -> Result<&str> {
+        self.header(name)
+            .ok_or_else(|| anyhow!("{} frame missing required header {:?}", self.command, name))
+    }
+
+    pub(crate) fn serialize(&self, dst: &mut BytesMut) {
+        dst.reserve(self.command.len() + self.body.len() + 32);
+        dst.put_slice(self.command.as_bytes());
+        dst.put_u8(b'\n');
+        for (key, value) in &self.headers {
+            dst.put_slice(key.as_bytes());
+            dst.put_u8(b':');
+            dst.put_slice(value.as_bytes());
+            dst.put_u8(b'\n');
+        }
+        dst.put_u8(b'\n');
+        dst.put_slice(&self.body);
+        dst.put_u8(0);
+    }
+}
+
+fn trim_cr(s: &[u8]) -> &[u8] {
+    s.strip_suffix(b"\r").unwrap_or(s)
+}
+
+fn parse_header(input: &[u8]) -> IResult<&[u8], (String, String)> {
+    let (input, key) = is_not(":\n")(input)?;
+    let (input, _) = tag(":")(input)?;
+    let (input, value) = is_not("\n")(input)?;
+    let (input, _) = tag("\n")(input)?;
+    Ok((
+        input,
+        (
+            String::from_utf8_lossy(trim_cr(key)).into_owned(),
+            String::from_utf8_lossy(trim_cr(value)).into_owned(),
+        ),
+    ))
+}
+
+/// Parse one frame out of `input`. Any leading heart-beat bytes must
+/// already have been skipped by the caller.
+pub fn parse_frame(input: &[u8]) -> IResult<&[u8], Frame> {
+    let (input, command) = terminated(is_not("\n"), tag("\n"))(input)?;
+    let (input, headers) = many0(parse_header)(input)?;
+    let (input, _) = tag("\n")(input)?;
+    let (input, body) = terminated(take_until("\x00"), tag("\x00"))(input)?;
+    Ok((
+        input,
+        Frame {
+            command: String::from_utf8_lossy(trim_cr(command)).into_owned(),
+            headers,
+            body: body.to_vec(),
+        },
+    ))
+}
+
+fn parse_heartbeat(value: &str) -> Option<(u32, u32)> {
+    let (x, y) = value.split_once(',')?;
+    Some((x.parse().ok()?, y.parse().ok()?))
+}
+
+fn format_heartbeat((x, y): (u32, u32)) -> String {
+    format!("{x},{y}")
+}
+
+/// Convert a content enum into the `Frame` that carries it on the wire.
+pub(crate) trait ToFrame {
+    fn to_frame(&self) -> Frame;
+}
+
+/// Recover a content enum from a parsed `Frame`.
+pub(crate) trait FromFrame: Sized {
+    fn from_frame(frame: Frame) -> Result<Self>;
+}
+
+// `ToFrame`/`FromFrame` are crate-internal, but these inherent methods on the
+// public `Message<T>` are themselves only `pub(crate)`, so the bound can't
+// leak outside the crate.
+#[allow(private_bounds)]
+impl<T: ToFrame> Message<T> {
+    pub(crate) fn to_frame(&self) -> Frame {
+        let mut frame = self.content.to_frame();
+        frame.headers.extend(self.extra_headers.iter().cloned());
+        frame
+    }
+}
+
+#[allow(private_bounds)]
+impl<T: FromFrame> Message<T> {
+    pub(crate) fn from_frame(frame: Frame) -> Result<Message<T>> {
+        Ok(Message {
+            content: T::from_frame(frame)?,
+            extra_headers: vec![],
+        })
+    }
+}
+
+impl ToFrame for ToServer {
+    fn to_frame(&self) -> Frame {
+        match self {
+            ToServer::Connect {
+                accept_version,
+                host,
+                login,
+                passcode,
+                heartbeat,
+            } => Frame::new(
+                "CONNECT",
+                vec![
+                    ("accept-version", Some(accept_version.clone())),
+                    ("host", Some(host.clone())),
+                    ("login", login.clone()),
+                    ("passcode", passcode.clone()),
+                    ("heart-beat", heartbeat.map(format_heartbeat)),
+                ],
+                vec![],
+            ),
+            ToServer::Disconnect { receipt } => {
+                Frame::new("DISCONNECT", vec![("receipt", receipt.clone())], vec![])
+            }
+            ToServer::Subscribe {
+                destination,
+                id,
+                ack,
+            } => Frame::new(
+                "SUBSCRIBE",
+                vec![
+                    ("destination", Some(destination.clone())),
+                    ("id", Some(id.clone())),
+                    ("ack", ack.map(|a| a.as_header_value().to_string())),
+                ],
+                vec![],
+            ),
+            ToServer::Unsubscribe { id } => {
+                Frame::new("UNSUBSCRIBE", vec![("id", Some(id.clone()))], vec![])
+            }
+            ToServer::Send {
+                destination,
+                transaction,
+                body,
+            } => Frame::new(
+                "SEND",
+                vec![
+                    ("destination", Some(destination.clone())),
+                    ("transaction", transaction.clone()),
+                    ("content-length", Some(body.len().to_string())),
+                ],
+                body.clone(),
+            ),
+            ToServer::Ack { id, transaction } => Frame::new(
+                "ACK",
+                vec![
+                    ("id", Some(id.clone())),
+                    ("transaction", transaction.clone()),
+                ],
+                vec![],
+            ),
+            ToServer::Nack { id, transaction } => Frame::new(
+                "NACK",
+                vec![
+                    ("id", Some(id.clone())),
+                    ("transaction", transaction.clone()),
+                ],
+                vec![],
+            ),
+            ToServer::Begin { transaction } => Frame::new(
+                "BEGIN",
+                vec![("transaction", Some(transaction.clone()))],
+                vec![],
+            ),
+            ToServer::Commit { transaction } => Frame::new(
+                "COMMIT",
+                vec![("transaction", Some(transaction.clone()))],
+                vec![],
+            ),
+            ToServer::Abort { transaction } => Frame::new(
+                "ABORT",
+                vec![("transaction", Some(transaction.clone()))],
+                vec![],
+            ),
+        }
+    }
+}
+
+impl FromFrame for ToServer {
+    fn from_frame(frame: Frame) -> Result<Self> {
+        let owned = |name| frame.header(name).map(str::to_string);
+        Ok(match frame.command.as_str() {
+            "CONNECT" | "STOMP" => ToServer::Connect {
+                accept_version: frame.require_header("accept-version")?.to_string(),
+                host: frame.require_header("host")?.to_string(),
+                login: owned("login"),
+                passcode: owned("passcode"),
+                heartbeat: frame.header("heart-beat").and_then(parse_heartbeat),
+            },
+            "DISCONNECT" => ToServer::Disconnect {
+                receipt: owned("receipt"),
+            },
+            "SUBSCRIBE" => ToServer::Subscribe {
+                destination: frame.require_header("destination")?.to_string(),
+                id: frame.require_header("id")?.to_string(),
+                ack: frame.header("ack").and_then(|ack| match ack {
+                    "auto" => Some(AckMode::Auto),
+                    "client" => Some(AckMode::Client),
+                    "client-individual" => Some(AckMode::ClientIndividual),
+                    _ => None,
+                }),
+            },
+            "UNSUBSCRIBE" => ToServer::Unsubscribe {
+                id: frame.require_header("id")?.to_string(),
+            },
+            "SEND" => ToServer::Send {
+                destination: frame.require_header("destination")?.to_string(),
+                transaction: owned("transaction"),
+                body: frame.body.clone(),
+            },
+            "ACK" => ToServer::Ack {
+                id: frame.require_header("id")?.to_string(),
+                transaction: owned("transaction"),
+            },
+            "NACK" => ToServer::Nack {
+                id: frame.require_header("id")?.to_string(),
+                transaction: owned("transaction"),
+            },
+            "BEGIN" => ToServer::Begin {
+                transaction: frame.require_header("transaction")?.to_string(),
+            },
+            "COMMIT" => ToServer::Commit {
+                transaction: frame.require_header("transaction")?.to_string(),
+            },
+            "ABORT" => ToServer::Abort {
+                transaction: frame.require_header("transaction")?.to_string(),
+            },
+            other => bail!("unexpected client frame: {other:?}"),
+        })
+    }
+}
+
+impl ToFrame for FromServer {
+    fn to_frame(&self) -> Frame {
+        match self {
+            FromServer::Connected {
+                version,
+                session,
+                server,
+                heartbeat,
+            } => Frame::new(
+                "CONNECTED",
+                vec![
+                    ("version", Some(version.clone())),
+                    ("session", session.clone()),
+                    ("server", server.clone()),
+                    ("heart-beat", heartbeat.map(format_heartbeat)),
+                ],
+                vec![],
+            ),
+            FromServer::Message {
+                destination,
+                message_id,
+                subscription,
+                body,
+            } => Frame::new(
+                "MESSAGE",
+                vec![
+                    ("destination", Some(destination.clone())),
+                    ("message-id", Some(message_id.clone())),
+                    ("subscription", Some(subscription.clone())),
+                    ("content-length", Some(body.len().to_string())),
+                ],
+                body.clone(),
+            ),
+            FromServer::Receipt { receipt_id } => Frame::new(
+                "RECEIPT",
+                vec![("receipt-id", Some(receipt_id.clone()))],
+                vec![],
+            ),
+            FromServer::Error { message, body } => Frame::new(
+                "ERROR",
+                vec![
+                    ("message", message.clone()),
+                    ("content-length", Some(body.len().to_string())),
+                ],
+                body.clone(),
+            ),
+        }
+    }
+}
+
+impl FromFrame for FromServer {
+    fn from_frame(frame: Frame) -> Result<Self> {
+        let owned = |name| frame.header(name).map(str::to_string);
+        Ok(match frame.command.as_str() {
+            "CONNECTED" => FromServer::Connected {
+                version: frame.require_header("version")?.to_string(),
+                session: owned("session"),
+                server: owned("server"),
+                heartbeat: frame.header("heart-beat").and_then(parse_heartbeat),
+            },
+            "MESSAGE" => FromServer::Message {
+                destination: frame.require_header("destination")?.to_string(),
+                message_id: frame.require_header("message-id")?.to_string(),
+                subscription: frame.require_header("subscription")?.to_string(),
+                body: frame.body.clone(),
+            },
+            "RECEIPT" => FromServer::Receipt {
+                receipt_id: frame.require_header("receipt-id")?.to_string(),
+            },
+            "ERROR" => FromServer::Error {
+                message: owned("message"),
+                body: frame.body.clone(),
+            },
+            other => bail!("unexpected server frame: {other:?}"),
+        })
+    }
+}
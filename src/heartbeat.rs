@@ -0,0 +1,376 @@
+//! STOMP heart-beat negotiation and liveness monitoring.
+//!
+//! The STOMP 1.2 `heart-beat` header lets client and server each advertise
+//! how often they can send a frame (or a bare `\n`) and how often they
+//! expect one. [`Heartbeat::negotiate`] combines what we asked for with what
+//! the server reported in its `CONNECTED` frame into the effective
+//! intervals; [`HeartbeatTransport`] then runs a background task that emits
+//! a bare-newline heart-beat whenever nothing else has been sent for the
+//! `send` interval, and a liveness check that errors the stream out if
+//! nothing at all (data or heart-beat) has arrived within a grace window of
+//! the `expect` interval.
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::BytesMut;
+use futures::prelude::*;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Instant};
+use tokio_util::codec::{Decoder, Encoder, Framed};
+
+use crate::client::ClientCodec;
+use crate::server::ServerCodec;
+use crate::{FromServer, Message, Result, ToServer};
+use anyhow::anyhow;
+
+/// Negotiated heart-beat intervals, in milliseconds. A value of `0` means
+/// that direction is disabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Heartbeat {
+    /// How often we must send something (a frame or a bare heart-beat).
+    pub send_ms: u64,
+    /// How often we expect to hear something from the server.
+    pub expect_ms: u64,
+}
+
+impl Heartbeat {
+    /// Negotiate effective intervals from what we requested (`cx, cy`) and
+    /// what the server reported in its `CONNECTED` frame (`sx, sy`):
+    /// `send = max(cx, sy)`, `expect = max(cy, sx)`, with a `0` on either
+    /// side disabling that direction.
+    pub fn negotiate(requested: (u32, u32), server: (u32, u32)) -> Self {
+        let (cx, cy) = requested;
+        let (sx, sy) = server;
+        let send_ms = if cx == 0 || sy == 0 {
+            0
+        } else {
+            cx.max(sy) as u64
+        };
+        let expect_ms = if cy == 0 || sx == 0 {
+            0
+        } else {
+            cy.max(sx) as u64
+        };
+        Heartbeat { send_ms, expect_ms }
+    }
+}
+
+/// Grace period added on top of the negotiated `expect` interval before a
+/// missed heart-beat is treated as a dead connection.
+const EXPECT_TOLERANCE: Duration = Duration::from_millis(500);
+
+type LastSeen = Arc<Mutex<Instant>>;
+
+/// Wraps a transport's I/O so every byte actually read off the wire (frame
+/// or bare heart-beat) updates a shared timestamp, independent of whether
+/// the codec turns it into a decoded item.
+pub(crate) struct LivenessIo<T> {
+    inner: T,
+    last_seen: LastSeen,
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRead for LivenessIo<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if poll.is_ready() && buf.filled().len() > before {
+            *this.last_seen.lock().unwrap() = Instant::now();
+        }
+        poll
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncWrite for LivenessIo<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// Wrap `io` so its liveness can be tracked, ready to be handed to
+/// `ClientCodec.framed(..)` or `ServerCodec.framed(..)`. The returned
+/// [`LastSeen`] handle is later passed to [`HeartbeatTransport::spawn`]
+/// alongside the resulting `Framed`.
+pub(crate) fn wrap<T>(io: T) -> (LivenessIo<T>, LastSeen)
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let last_seen = Arc::new(Mutex::new(Instant::now()));
+    (
+        LivenessIo {
+            inner: io,
+            last_seen: last_seen.clone(),
+        },
+        last_seen,
+    )
+}
+
+/// Item sent over the underlying write half: either a real outgoing message
+/// or a bare heart-beat newline. Generic so the same writer task drives both
+/// [`ClientCodec`] (sending `Message<ToServer>`) and [`ServerCodec`]
+/// (sending `Message<FromServer>`).
+pub(crate) enum OutFrame<M> {
+    Message(M),
+    Heartbeat,
+}
+
+impl Encoder<OutFrame<Message<ToServer>>> for ClientCodec {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, item: OutFrame<Message<ToServer>>, dst: &mut BytesMut) -> Result<()> {
+        match item {
+            OutFrame::Message(msg) => Encoder::<Message<ToServer>>::encode(self, msg, dst),
+            OutFrame::Heartbeat => {
+                dst.extend_from_slice(b"\n");
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Encoder<OutFrame<Message<FromServer>>> for ServerCodec {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, item: OutFrame<Message<FromServer>>, dst: &mut BytesMut) -> Result<()> {
+        match item {
+            OutFrame::Message(msg) => Encoder::<Message<FromServer>>::encode(self, msg, dst),
+            OutFrame::Heartbeat => {
+                dst.extend_from_slice(b"\n");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A [`Stream`]/[`Sink`] pair wrapping a STOMP transport with the negotiated
+/// heart-beat send and liveness-check tasks, in place of a bare `Framed`
+/// transport. `Out` is the message type sent into this transport (`Sink`
+/// item); `In` is the message type read out of it (`Stream` item).
+///
+/// [`crate::client::connect`]/[`crate::client::connect_tls`]/
+/// [`crate::client::connect_rustls`] return
+/// `HeartbeatTransport<Message<ToServer>, Message<FromServer>>`;
+/// [`crate::server::accept`]/[`crate::server::accept_tls`] return the
+/// mirror image, `HeartbeatTransport<Message<FromServer>, Message<ToServer>>`,
+/// so a server built on this crate also honors whatever heart-beat it
+/// advertised in its `CONNECTED` frame.
+pub struct HeartbeatTransport<Out, In> {
+    outgoing: mpsc::UnboundedSender<Out>,
+    incoming: mpsc::UnboundedReceiver<Result<In>>,
+}
+
+impl<Out, In> HeartbeatTransport<Out, In>
+where
+    Out: Send + 'static,
+    In: Send + 'static,
+{
+    pub(crate) fn spawn<T, C>(
+        transport: Framed<LivenessIo<T>, C>,
+        last_seen: LastSeen,
+        heartbeat: Heartbeat,
+    ) -> Self
+    where
+        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        C: Decoder<Item = In, Error = anyhow::Error>
+            + Encoder<OutFrame<Out>, Error = anyhow::Error>
+            + Send
+            + 'static,
+    {
+        let (sink, stream) = transport.split::<OutFrame<Out>>();
+        let (out_tx, out_rx) = mpsc::unbounded_channel::<Out>();
+        let (in_tx, in_rx) = mpsc::unbounded_channel::<Result<In>>();
+
+        tokio::spawn(writer_task(sink, out_rx, heartbeat.send_ms, in_tx.clone()));
+        tokio::spawn(reader_task(stream, in_tx.clone()));
+        if heartbeat.expect_ms > 0 {
+            tokio::spawn(liveness_task(in_tx, last_seen, heartbeat.expect_ms));
+        }
+
+        HeartbeatTransport {
+            outgoing: out_tx,
+            incoming: in_rx,
+        }
+    }
+}
+
+impl<Out, In> Stream for HeartbeatTransport<Out, In> {
+    type Item = Result<In>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.incoming.poll_recv(cx)
+    }
+}
+
+impl<Out, In> Sink<Out> for HeartbeatTransport<Out, In> {
+    type Error = anyhow::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Out) -> Result<()> {
+        self.outgoing
+            .send(item)
+            .map_err(|_| anyhow!("heartbeat writer task has stopped"))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+async fn writer_task<Si, Out, In>(
+    mut sink: Si,
+    mut rx: mpsc::UnboundedReceiver<Out>,
+    send_ms: u64,
+    tx: mpsc::UnboundedSender<Result<In>>,
+) where
+    Si: Sink<OutFrame<Out>, Error = anyhow::Error> + Unpin,
+{
+    if send_ms == 0 {
+        while let Some(msg) = rx.recv().await {
+            if let Err(e) = sink.send(OutFrame::Message(msg)).await {
+                let _ = tx.send(Err(anyhow!("failed to write to transport: {e}")));
+                return;
+            }
+        }
+        return;
+    }
+    let interval = Duration::from_millis(send_ms);
+    let mut deadline = Instant::now() + interval;
+    loop {
+        tokio::select! {
+            msg = rx.recv() => {
+                match msg {
+                    Some(msg) => {
+                        if let Err(e) = sink.send(OutFrame::Message(msg)).await {
+                            let _ = tx.send(Err(anyhow!("failed to write to transport: {e}")));
+                            return;
+                        }
+                        deadline = Instant::now() + interval;
+                    }
+                    None => return,
+                }
+            }
+            _ = sleep_until(deadline) => {
+                if let Err(e) = sink.send(OutFrame::Heartbeat).await {
+                    let _ = tx.send(Err(anyhow!("failed to write heart-beat to transport: {e}")));
+                    return;
+                }
+                deadline = Instant::now() + interval;
+            }
+        }
+    }
+}
+
+async fn reader_task<St, In>(mut stream: St, tx: mpsc::UnboundedSender<Result<In>>)
+where
+    St: Stream<Item = Result<In>> + Unpin,
+{
+    while let Some(item) = stream.next().await {
+        if tx.send(item).is_err() {
+            return;
+        }
+    }
+}
+
+async fn liveness_task<In>(
+    tx: mpsc::UnboundedSender<Result<In>>,
+    last_seen: LastSeen,
+    expect_ms: u64,
+) {
+    let tolerance = Duration::from_millis(expect_ms) + EXPECT_TOLERANCE;
+    let mut ticker = tokio::time::interval(Duration::from_millis(expect_ms.max(100)));
+    loop {
+        ticker.tick().await;
+        let elapsed = last_seen.lock().unwrap().elapsed();
+        if elapsed > tolerance {
+            let _ = tx.send(Err(anyhow!(
+                "no data or heart-beat received from the peer in {:?} (expected every {}ms)",
+                elapsed,
+                expect_ms
+            )));
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate_both_disabled() {
+        let hb = Heartbeat::negotiate((0, 0), (0, 0));
+        assert_eq!(
+            hb,
+            Heartbeat {
+                send_ms: 0,
+                expect_ms: 0
+            }
+        );
+    }
+
+    #[test]
+    fn negotiate_takes_the_max_of_each_direction() {
+        let hb = Heartbeat::negotiate((1000, 2000), (1500, 500));
+        // send = max(cx, sy) = max(1000, 500) = 1000
+        // expect = max(cy, sx) = max(2000, 1500) = 2000
+        assert_eq!(
+            hb,
+            Heartbeat {
+                send_ms: 1000,
+                expect_ms: 2000
+            }
+        );
+    }
+
+    #[test]
+    fn negotiate_zero_cx_disables_send() {
+        let hb = Heartbeat::negotiate((0, 2000), (1500, 500));
+        assert_eq!(hb.send_ms, 0);
+        assert_eq!(hb.expect_ms, 2000);
+    }
+
+    #[test]
+    fn negotiate_zero_sy_disables_send() {
+        let hb = Heartbeat::negotiate((1000, 2000), (1500, 0));
+        assert_eq!(hb.send_ms, 0);
+    }
+
+    #[test]
+    fn negotiate_zero_cy_disables_expect() {
+        let hb = Heartbeat::negotiate((1000, 0), (1500, 500));
+        assert_eq!(hb.expect_ms, 0);
+    }
+
+    #[test]
+    fn negotiate_zero_sx_disables_expect() {
+        let hb = Heartbeat::negotiate((1000, 2000), (0, 500));
+        assert_eq!(hb.expect_ms, 0);
+    }
+}
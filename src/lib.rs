@@ -0,0 +1,133 @@
+//! A STOMP 1.2 client (and server helpers) for Tokio.
+//!
+//! The crate root defines the wire-independent message types shared by
+//! [`client`] and [`server`] — [`ToServer`], [`FromServer`], and the
+//! [`Message`] wrapper that carries them alongside any extra headers.
+//! [`frame`] handles the actual STOMP frame syntax (parsing and
+//! serialization); [`heartbeat`] and [`reconnect`] build heart-beat
+//! enforcement and auto-reconnect support on top.
+
+pub mod client;
+pub mod frame;
+pub mod heartbeat;
+pub mod reconnect;
+pub mod server;
+
+/// This crate's result type. STOMP errors (protocol violations, unexpected
+/// frames, I/O failures) are all reported as an [`anyhow::Error`].
+pub type Result<T> = anyhow::Result<T>;
+
+/// Acknowledgement mode for a `SUBSCRIBE` frame's `ack` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckMode {
+    Auto,
+    Client,
+    ClientIndividual,
+}
+
+impl AckMode {
+    pub(crate) fn as_header_value(self) -> &'static str {
+        match self {
+            AckMode::Auto => "auto",
+            AckMode::Client => "client",
+            AckMode::ClientIndividual => "client-individual",
+        }
+    }
+}
+
+/// A client-to-server STOMP frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToServer {
+    Connect {
+        accept_version: String,
+        host: String,
+        login: Option<String>,
+        passcode: Option<String>,
+        heartbeat: Option<(u32, u32)>,
+    },
+    Disconnect {
+        receipt: Option<String>,
+    },
+    Subscribe {
+        destination: String,
+        id: String,
+        ack: Option<AckMode>,
+    },
+    Unsubscribe {
+        id: String,
+    },
+    Send {
+        destination: String,
+        transaction: Option<String>,
+        body: Vec<u8>,
+    },
+    Ack {
+        id: String,
+        transaction: Option<String>,
+    },
+    Nack {
+        id: String,
+        transaction: Option<String>,
+    },
+    Begin {
+        transaction: String,
+    },
+    Commit {
+        transaction: String,
+    },
+    Abort {
+        transaction: String,
+    },
+}
+
+/// A server-to-client STOMP frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FromServer {
+    Connected {
+        version: String,
+        session: Option<String>,
+        server: Option<String>,
+        heartbeat: Option<(u32, u32)>,
+    },
+    Message {
+        destination: String,
+        message_id: String,
+        subscription: String,
+        body: Vec<u8>,
+    },
+    Receipt {
+        receipt_id: String,
+    },
+    Error {
+        message: Option<String>,
+        body: Vec<u8>,
+    },
+}
+
+/// A STOMP frame's content, paired with any extra headers the caller wants
+/// sent alongside the ones this crate understands. Decoded messages always
+/// have an empty `extra_headers`; headers this crate does not understand are
+/// not kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message<T> {
+    pub content: T,
+    pub extra_headers: Vec<(String, String)>,
+}
+
+impl From<ToServer> for Message<ToServer> {
+    fn from(content: ToServer) -> Self {
+        Message {
+            content,
+            extra_headers: vec![],
+        }
+    }
+}
+
+impl From<FromServer> for Message<FromServer> {
+    fn from(content: FromServer) -> Self {
+        Message {
+            content,
+            extra_headers: vec![],
+        }
+    }
+}
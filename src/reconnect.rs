@@ -0,0 +1,344 @@
+//! Automatic reconnection on top of [`crate::client`].
+//!
+//! [`ReconnectingClient`] owns the connection parameters (address, login,
+//! passcode, heart-beat request, TLS config) and transparently re-establishes
+//! the transport after an I/O error or heart-beat timeout, replaying any
+//! subscriptions made through [`ReconnectingClient::subscribe`] once the new
+//! connection is up.
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::prelude::*;
+use tokio::sync::mpsc;
+
+use crate::client::{self, ClientTransport, TlsConfig};
+use crate::{FromServer, Message, Result, ToServer};
+use anyhow::anyhow;
+
+/// Exponential backoff applied between reconnect attempts.
+#[derive(Debug, Clone, Copy)]
+pub struct BackoffPolicy {
+    pub initial_delay: Duration,
+    pub multiplier: f64,
+    pub max_delay: Duration,
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        BackoffPolicy {
+            initial_delay: Duration::from_millis(500),
+            multiplier: 2.0,
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl BackoffPolicy {
+    pub fn new(initial_delay: Duration, multiplier: f64, max_delay: Duration) -> Self {
+        BackoffPolicy {
+            initial_delay,
+            multiplier,
+            max_delay,
+        }
+    }
+
+    fn next_delay(&self, current: Duration) -> Duration {
+        current.mul_f64(self.multiplier).min(self.max_delay)
+    }
+}
+
+/// Which transport a [`ReconnectingClient`] should (re-)establish.
+enum Endpoint {
+    Plain,
+    Tls {
+        domain: String,
+        tls_config: TlsConfig,
+    },
+}
+
+/// Connection parameters remembered across reconnects.
+struct ConnectParams {
+    address: String,
+    login: Option<String>,
+    passcode: Option<String>,
+    heartbeat: Option<(u32, u32)>,
+    endpoint: Endpoint,
+}
+
+impl ConnectParams {
+    async fn dial(&self) -> Result<ClientTransport> {
+        match &self.endpoint {
+            Endpoint::Plain => {
+                client::connect(
+                    &self.address,
+                    self.login.clone(),
+                    self.passcode.clone(),
+                    self.heartbeat,
+                )
+                .await
+            }
+            Endpoint::Tls { domain, tls_config } => {
+                client::connect_tls(
+                    domain,
+                    &self.address,
+                    self.login.clone(),
+                    self.passcode.clone(),
+                    tls_config.clone(),
+                    self.heartbeat,
+                )
+                .await
+            }
+        }
+    }
+}
+
+type TrackedSubscriptions = Arc<Mutex<Vec<(String, String)>>>;
+
+/// A STOMP client that transparently reconnects after an I/O error or
+/// heart-beat timeout.
+///
+/// The returned [`Stream`]/[`Sink`] handle stays valid across reconnects: a
+/// supervisor task owns the current [`ClientTransport`] and re-dials a fresh
+/// one as needed, so callers don't have to tear down and rebuild their
+/// pipelines on a transient TLS/TCP drop. Subscriptions made through
+/// [`ReconnectingClient::subscribe`] are re-issued after every successful
+/// re-handshake.
+pub struct ReconnectingClient {
+    outgoing: mpsc::UnboundedSender<Message<ToServer>>,
+    incoming: mpsc::UnboundedReceiver<Result<Message<FromServer>>>,
+    subscriptions: TrackedSubscriptions,
+}
+
+impl ReconnectingClient {
+    async fn new(params: ConnectParams, backoff: BackoffPolicy) -> Result<Self> {
+        // The first attempt is not retried: a persistently bad address or
+        // config should surface immediately rather than loop forever.
+        let transport = params.dial().await?;
+
+        let (out_tx, out_rx) = mpsc::unbounded_channel();
+        let (in_tx, in_rx) = mpsc::unbounded_channel();
+        let subscriptions: TrackedSubscriptions = Arc::new(Mutex::new(Vec::new()));
+
+        tokio::spawn(supervisor(
+            transport,
+            params,
+            backoff,
+            out_rx,
+            in_tx,
+            subscriptions.clone(),
+        ));
+
+        Ok(ReconnectingClient {
+            outgoing: out_tx,
+            incoming: in_rx,
+            subscriptions,
+        })
+    }
+
+    /// Like [`crate::client::connect`], but automatically reconnects using
+    /// `backoff` after a transport error or heart-beat timeout.
+    pub async fn connect(
+        address: impl Into<String>,
+        login: Option<String>,
+        passcode: Option<String>,
+        heartbeat: Option<(u32, u32)>,
+        backoff: BackoffPolicy,
+    ) -> Result<Self> {
+        let params = ConnectParams {
+            address: address.into(),
+            login,
+            passcode,
+            heartbeat,
+            endpoint: Endpoint::Plain,
+        };
+        Self::new(params, backoff).await
+    }
+
+    /// Like [`crate::client::connect_tls`], but automatically reconnects
+    /// using `backoff` after a transport error or heart-beat timeout.
+    pub async fn connect_tls(
+        domain: impl Into<String>,
+        address: impl Into<String>,
+        login: Option<String>,
+        passcode: Option<String>,
+        tls_config: TlsConfig,
+        heartbeat: Option<(u32, u32)>,
+        backoff: BackoffPolicy,
+    ) -> Result<Self> {
+        let params = ConnectParams {
+            address: address.into(),
+            login,
+            passcode,
+            heartbeat,
+            endpoint: Endpoint::Tls {
+                domain: domain.into(),
+                tls_config,
+            },
+        };
+        Self::new(params, backoff).await
+    }
+
+    /// Subscribe to `dest`, remembering the subscription so it is re-issued
+    /// automatically after every reconnect. A later call with the same `id`
+    /// replaces the tracked destination rather than replaying both.
+    pub fn subscribe(&self, dest: impl Into<String>, id: impl Into<String>) -> Result<()> {
+        let dest = dest.into();
+        let id = id.into();
+        {
+            let mut subs = self.subscriptions.lock().unwrap();
+            subs.retain(|(_, existing_id)| existing_id != &id);
+            subs.push((dest.clone(), id.clone()));
+        }
+        self.outgoing
+            .send(client::subscribe(dest, id))
+            .map_err(|_| anyhow!("reconnecting client has stopped"))
+    }
+
+    /// Unsubscribe from `id`, stopping it from being replayed on future
+    /// reconnects, and send the corresponding `UNSUBSCRIBE` frame.
+    pub fn unsubscribe(&self, id: impl Into<String>) -> Result<()> {
+        let id = id.into();
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .retain(|(_, existing_id)| existing_id != &id);
+        self.outgoing
+            .send(ToServer::Unsubscribe { id }.into())
+            .map_err(|_| anyhow!("reconnecting client has stopped"))
+    }
+}
+
+impl Stream for ReconnectingClient {
+    type Item = Result<Message<FromServer>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.incoming.poll_recv(cx)
+    }
+}
+
+impl Sink<Message<ToServer>> for ReconnectingClient {
+    type Error = anyhow::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Message<ToServer>) -> Result<()> {
+        self.outgoing
+            .send(item)
+            .map_err(|_| anyhow!("reconnecting client has stopped"))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+async fn supervisor(
+    mut transport: ClientTransport,
+    params: ConnectParams,
+    backoff: BackoffPolicy,
+    mut out_rx: mpsc::UnboundedReceiver<Message<ToServer>>,
+    in_tx: mpsc::UnboundedSender<Result<Message<FromServer>>>,
+    subscriptions: TrackedSubscriptions,
+) {
+    // A message that failed to go out before the last reconnect; it is the
+    // next thing sent once a new transport is up, ahead of anything else
+    // waiting in `out_rx`.
+    let mut pending: Option<Message<ToServer>> = None;
+
+    loop {
+        if let Some(msg) = pending.take() {
+            if let Err(e) = transport.send(msg.clone()).await {
+                let _ = in_tx.send(Err(anyhow!("failed to send, reconnecting: {e}")));
+                pending = Some(msg);
+                match reconnect(&params, &backoff, &subscriptions, &in_tx).await {
+                    Some(new_transport) => transport = new_transport,
+                    None => return,
+                }
+            }
+            continue;
+        }
+
+        let needs_reconnect = tokio::select! {
+            outgoing = out_rx.recv() => {
+                match outgoing {
+                    Some(msg) => {
+                        if let Err(e) = transport.send(msg.clone()).await {
+                            let _ = in_tx.send(Err(anyhow!("failed to send, reconnecting: {e}")));
+                            pending = Some(msg);
+                            true
+                        } else {
+                            false
+                        }
+                    }
+                    // The user dropped their handle; nothing left to do.
+                    None => return,
+                }
+            }
+            incoming = transport.next() => {
+                match incoming {
+                    Some(Ok(msg)) => {
+                        let _ = in_tx.send(Ok(msg));
+                        false
+                    }
+                    Some(Err(e)) => {
+                        let _ = in_tx.send(Err(anyhow!("transport error, reconnecting: {e}")));
+                        true
+                    }
+                    None => true,
+                }
+            }
+        };
+
+        if !needs_reconnect {
+            continue;
+        }
+
+        match reconnect(&params, &backoff, &subscriptions, &in_tx).await {
+            Some(new_transport) => transport = new_transport,
+            None => return,
+        }
+    }
+}
+
+/// Re-dial with exponential backoff and replay tracked subscriptions on the
+/// new transport. Transient TCP/TLS failures are expected to eventually
+/// resolve, so this retries indefinitely — `backoff.max_delay` bounds how
+/// long it waits between attempts — unless `in_tx` is closed, meaning the
+/// caller dropped their [`ReconnectingClient`] and there is no one left to
+/// hand a transport to.
+async fn reconnect(
+    params: &ConnectParams,
+    backoff: &BackoffPolicy,
+    subscriptions: &TrackedSubscriptions,
+    in_tx: &mpsc::UnboundedSender<Result<Message<FromServer>>>,
+) -> Option<ClientTransport> {
+    let mut delay = backoff.initial_delay;
+    let mut transport = loop {
+        if in_tx.is_closed() {
+            return None;
+        }
+        match params.dial().await {
+            Ok(transport) => break transport,
+            Err(_) => {
+                tokio::time::sleep(delay).await;
+                delay = backoff.next_delay(delay);
+            }
+        }
+    };
+
+    let subs = subscriptions.lock().unwrap().clone();
+    for (dest, id) in subs {
+        if transport.send(client::subscribe(dest, id)).await.is_err() {
+            break;
+        }
+    }
+    Some(transport)
+}
@@ -0,0 +1,128 @@
+use bytes::{Buf, BytesMut};
+use futures::prelude::*;
+use futures::sink::SinkExt;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_util::codec::{Decoder, Encoder, Framed};
+
+use tokio_native_tls::TlsAcceptor;
+
+use crate::client::skip_heartbeats;
+use crate::frame;
+use crate::heartbeat::Heartbeat;
+use crate::{FromServer, Message, Result, ToServer};
+use anyhow::bail;
+
+/// A connected, authenticated STOMP transport as seen from the server side:
+/// a [`Stream`] of `Result<Message<ToServer>>` and a [`Sink`] of
+/// `Message<FromServer>`, backed by [`crate::heartbeat::HeartbeatTransport`]
+/// so that the heart-beat this server advertised in its `CONNECTED` frame is
+/// actually sent and enforced, rather than only echoed.
+pub type ServerTransport =
+    crate::heartbeat::HeartbeatTransport<Message<FromServer>, Message<ToServer>>;
+pub type ServerTlsTransport =
+    crate::heartbeat::HeartbeatTransport<Message<FromServer>, Message<ToServer>>;
+
+/// Wrap an accepted TCP connection, performing the server half of the
+/// CONNECT/CONNECTED handshake. Mirrors [`crate::client::connect`].
+///
+/// `heartbeat`, if given, is the `(sx, sy)` pair (in milliseconds) this
+/// server advertises in its `CONNECTED` frame: `sx` is the minimum interval
+/// between frames it guarantees to send, `sy` is the interval it wants from
+/// the client. The effective intervals are negotiated against whatever the
+/// client asked for in its `CONNECT` frame (see [`Heartbeat::negotiate`])
+/// and then actually enforced by the returned transport, the same way
+/// [`crate::client::connect`] enforces what it negotiated.
+pub async fn accept(tcp: TcpStream, heartbeat: Option<(u32, u32)>) -> Result<ServerTransport> {
+    let (io, last_seen) = crate::heartbeat::wrap(tcp);
+    let mut transport = ServerCodec.framed(io);
+    let negotiated = server_handshake(&mut transport, heartbeat).await?;
+    Ok(crate::heartbeat::HeartbeatTransport::spawn(
+        transport, last_seen, negotiated,
+    ))
+}
+
+/// Wrap an accepted TCP connection in TLS using `acceptor`, then perform the
+/// server half of the CONNECT/CONNECTED handshake. Mirrors
+/// [`crate::client::connect_tls`]. See [`accept`] for `heartbeat`.
+pub async fn accept_tls(
+    acceptor: &TlsAcceptor,
+    tcp: TcpStream,
+    heartbeat: Option<(u32, u32)>,
+) -> Result<ServerTlsTransport> {
+    let tls_stream = acceptor.accept(tcp).await?;
+    let (io, last_seen) = crate::heartbeat::wrap(tls_stream);
+    let mut transport = ServerCodec.framed(io);
+    let negotiated = server_handshake(&mut transport, heartbeat).await?;
+    Ok(crate::heartbeat::HeartbeatTransport::spawn(
+        transport, last_seen, negotiated,
+    ))
+}
+
+async fn server_handshake<T>(
+    transport: &mut Framed<crate::heartbeat::LivenessIo<T>, ServerCodec>,
+    heartbeat: Option<(u32, u32)>,
+) -> Result<Heartbeat>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    // Receive CONNECT
+    let msg = transport.next().await.transpose()?;
+    let client_heartbeat = match msg.as_ref().map(|m| &m.content) {
+        Some(ToServer::Connect { heartbeat, .. }) => *heartbeat,
+        _ => bail!("expected CONNECT frame, got: {:?}", msg),
+    };
+    // Reply CONNECTED
+    let connected = Message {
+        content: FromServer::Connected {
+            version: "1.2".into(),
+            session: None,
+            server: None,
+            heartbeat,
+        },
+        extra_headers: vec![],
+    };
+    transport.send(connected).await?;
+    Ok(Heartbeat::negotiate(
+        heartbeat.unwrap_or((0, 0)),
+        client_heartbeat.unwrap_or((0, 0)),
+    ))
+}
+
+/// The server-side counterpart of [`crate::client::ClientCodec`]: decodes
+/// frames sent by clients and encodes frames sent by the server.
+pub struct ServerCodec;
+
+impl Decoder for ServerCodec {
+    type Item = Message<ToServer>;
+    type Error = anyhow::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>> {
+        if skip_heartbeats(src) {
+            return Ok(None);
+        }
+        let (item, offset) = match frame::parse_frame(src) {
+            Ok((remain, frame)) => (
+                Message::<ToServer>::from_frame(frame),
+                remain.as_ptr() as usize - src.as_ptr() as usize,
+            ),
+            Err(nom::Err::Incomplete(_)) => return Ok(None),
+            Err(e) => bail!("Parse failed: {:?}", e),
+        };
+        src.advance(offset);
+        item.map(Some)
+    }
+}
+
+impl Encoder<Message<FromServer>> for ServerCodec {
+    type Error = anyhow::Error;
+
+    fn encode(
+        &mut self,
+        item: Message<FromServer>,
+        dst: &mut BytesMut,
+    ) -> std::result::Result<(), Self::Error> {
+        item.to_frame().serialize(dst);
+        Ok(())
+    }
+}